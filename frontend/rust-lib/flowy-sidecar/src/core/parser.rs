@@ -1,22 +1,64 @@
 use crate::core::rpc_object::RpcObject;
-use crate::core::rpc_peer::ResponsePayload;
-use crate::error::{ReadError, RemoteError};
+use crate::core::rpc_peer::{ResponsePayload, StreamHandlers};
+use crate::error::{JustId, ReadError, RemoteError};
 use serde_json::{json, Value as JsonValue};
 use std::io::BufRead;
 
+pub type RequestId = u64;
+
+/// A fully classified JSON-RPC 2.0 message, as produced by
+/// [`RpcObject::decode`] (or, for a [`Message::Batch`], by
+/// [`MessageReader::parse`]).
+#[derive(Debug, Clone)]
+pub enum Message {
+  /// A request: carries an `id` that the response must echo back.
+  Request {
+    id: RequestId,
+    method: String,
+    params: Option<JsonValue>,
+  },
+  /// A notification: has no `id`, so it expects no response.
+  Notification {
+    method: String,
+    params: Option<JsonValue>,
+  },
+  /// A response to a request this peer previously sent.
+  Response {
+    id: RequestId,
+    result: ResponsePayload,
+  },
+  /// A JSON-RPC 2.0 batch: several requests/notifications/responses sent
+  /// as a single top-level JSON array.
+  Batch(Vec<Message>),
+}
+
+impl Message {
+  /// Whether this message expects a response to be written back. A
+  /// batch expects one only if at least one of its elements does — a
+  /// batch made up entirely of notifications produces no response at all.
+  pub fn expects_response(&self) -> bool {
+    match self {
+      Message::Request { .. } => true,
+      Message::Notification { .. } => false,
+      Message::Response { .. } => false,
+      Message::Batch(messages) => messages.iter().any(Message::expects_response),
+    }
+  }
+}
+
 #[derive(Debug, Default)]
 pub struct MessageReader(String);
 
 impl MessageReader {
   /// Attempts to read the next line from the stream and parse it as
-  /// an RPC object.
+  /// a JSON-RPC 2.0 message.
   ///
   /// # Errors
   ///
   /// This function will return an error if there is an underlying
   /// I/O error, if the stream is closed, or if the message is not
   /// a valid JSON object.
-  pub fn next<R: BufRead>(&mut self, reader: &mut R) -> Result<RpcObject, ReadError> {
+  pub fn next<R: BufRead>(&mut self, reader: &mut R) -> Result<Message, ReadError> {
     self.0.clear();
     let _ = reader.read_line(&mut self.0)?;
     if self.0.is_empty() {
@@ -26,11 +68,128 @@ impl MessageReader {
     }
   }
 
-  /// Attempts to parse a &str as an RPC Object.
+  /// Reads the next message like [`MessageReader::next`], but routes any
+  /// `"stream"` notification through `handlers` instead of returning it,
+  /// so streamed chat tokens reach their handler as they arrive rather
+  /// than being buffered into one final message. Returns `Ok(None)` when
+  /// the line was consumed entirely as stream chunks (a lone chunk, or a
+  /// batch made up only of them); the caller should just read again.
+  pub fn next_dispatching_streams<R: BufRead>(
+    &mut self,
+    reader: &mut R,
+    handlers: &StreamHandlers,
+  ) -> Result<Option<Message>, ReadError> {
+    let message = self.next(reader)?;
+    Ok(Self::dispatch_stream_chunks(message, handlers))
+  }
+
+  /// Strips `"stream"` notifications out of `message`, dispatching each
+  /// one through `handlers` rather than letting it surface to the caller.
+  fn dispatch_stream_chunks(message: Message, handlers: &StreamHandlers) -> Option<Message> {
+    match message {
+      Message::Notification { method, params } if method == STREAM_METHOD => {
+        let params = params.unwrap_or(JsonValue::Null);
+        if let Some(id) = StreamResponseParser::stream_id(&params) {
+          if let Ok(chunk) = StreamResponseParser::parse_chunk(&params) {
+            handlers.dispatch(id, chunk);
+          }
+        }
+        None
+      },
+      Message::Batch(messages) => {
+        let remaining: Vec<Message> = messages
+          .into_iter()
+          .filter_map(|m| Self::dispatch_stream_chunks(m, handlers))
+          .collect();
+        if remaining.is_empty() {
+          None
+        } else {
+          Some(Message::Batch(remaining))
+        }
+      },
+      other => Some(other),
+    }
+  }
+
+  /// Attempts to parse a &str as a JSON-RPC 2.0 message, classifying it
+  /// as a [`Message::Request`], [`Message::Notification`], [`Message::Response`],
+  /// or — if the line is a top-level JSON array — a [`Message::Batch`] of
+  /// those, so the peer can dispatch accordingly.
+  ///
+  /// If the line cannot be classified (for instance the remote sent a
+  /// malformed response), this falls back to recovering just the `id`
+  /// so the caller can still fail the pending request it belongs to,
+  /// rather than leaving it waiting forever.
   ///
   /// This should not be called directly unless you are writing tests.
   #[doc(hidden)]
-  pub fn parse(&self, s: &str) -> Result<RpcObject, ReadError> {
+  pub fn parse(&self, s: &str) -> Result<Message, ReadError> {
+    if let Ok(JsonValue::Array(items)) = serde_json::from_str::<JsonValue>(s) {
+      return Self::decode_batch(items, s);
+    }
+
+    match self.parse_object(s).and_then(RpcObject::decode) {
+      Ok(message) => Ok(message),
+      Err(err) => match Self::recover_response_id(s) {
+        Some(id) => Ok(Message::Response {
+          id,
+          result: Err(RemoteError::InvalidResponse(json!({"parse_error": err.to_string()}))),
+        }),
+        None => Err(err),
+      },
+    }
+  }
+
+  /// Decodes a top-level JSON array into a [`Message::Batch`]. An empty
+  /// batch is an invalid request per the JSON-RPC 2.0 spec. Each element
+  /// is decoded independently — a malformed element becomes an error
+  /// [`Message::Response`] of its own (id recovered on a best-effort
+  /// basis) rather than discarding the whole batch, so the other,
+  /// well-formed elements still make it through. An element that fails to
+  /// decode and carries no recoverable id is dropped entirely rather than
+  /// resolving some unrelated, never-observed id with a bogus error.
+  fn decode_batch(items: Vec<JsonValue>, s: &str) -> Result<Message, ReadError> {
+    if items.is_empty() {
+      return Err(ReadError::NotObject(s.to_string()));
+    }
+
+    let messages = items.into_iter().filter_map(Self::decode_batch_item).collect();
+    Ok(Message::Batch(messages))
+  }
+
+  /// Decodes a single batch element, turning a decode failure into an
+  /// error response rather than propagating it and voiding the batch.
+  /// Returns `None` if the element fails to decode and has no recoverable
+  /// id, since there's no pending call to resolve and no id should be
+  /// fabricated.
+  fn decode_batch_item(item: JsonValue) -> Option<Message> {
+    let raw = item.to_string();
+    let decoded = if item.is_object() {
+      RpcObject::from(item).decode()
+    } else {
+      Err(ReadError::NotObject(raw.clone()))
+    };
+
+    match decoded {
+      Ok(message) => Some(message),
+      Err(err) => Self::recover_response_id(&raw).map(|id| Message::Response {
+        id,
+        result: Err(RemoteError::InvalidResponse(json!({"batch_item_error": err.to_string()}))),
+      }),
+    }
+  }
+
+  /// Does a minimal second-pass deserialize of just the `"id"` field of a
+  /// line that otherwise failed to parse as a [`Message`]. This recovers
+  /// cases `RpcObject::decode` gives up on entirely, such as a response
+  /// whose id was sent as a numeral string rather than a JSON number.
+  fn recover_response_id(s: &str) -> Option<RequestId> {
+    serde_json::from_str::<JustId>(s).ok().and_then(|just_id| just_id.as_request_id())
+  }
+
+  /// Attempts to parse a &str as a raw [`RpcObject`], without classifying
+  /// it. Exposed for callers that need the unclassified object.
+  pub(crate) fn parse_object(&self, s: &str) -> Result<RpcObject, ReadError> {
     match serde_json::from_str::<JsonValue>(s) {
       Ok(val) => {
         if !val.is_object() {
@@ -44,68 +203,258 @@ impl MessageReader {
   }
 }
 
-pub type RequestId = u64;
-#[derive(Debug, Clone)]
-/// An RPC call, which may be either a notification or a request.
-pub enum Call<R> {
-  Message(JsonValue),
-  /// An id and an RPC Request
-  Request(RequestId, R),
-  /// A malformed request: the request contained an id, but could
-  /// not be parsed. The client will receive an error.
-  InvalidRequest(RequestId, RemoteError),
-}
-
 pub trait ResponseParser {
   type ValueType;
   fn parse_response(payload: JsonValue) -> Result<Self::ValueType, RemoteError>;
 }
 
-pub struct ChatResponseParser;
-impl ResponseParser for ChatResponseParser {
-  type ValueType = String;
+/// If `json` carries a JSON-RPC 2.0 `"error"` member, decodes it into a
+/// structured [`RemoteError`]. Every [`ResponseParser`] checks this first
+/// so a server-reported error is never mistaken for a malformed payload.
+fn decode_error(json: &JsonValue) -> Option<RemoteError> {
+  json.get("error").cloned().map(RemoteError::from_error_object)
+}
+
+/// A [`ResponseParser`] for any `T` that can be deserialized directly from
+/// the response's `"data"` member (or the whole payload, if there is no
+/// `"data"` wrapper). Defining a new response type is then just a struct
+/// plus a type alias, with no hand-rolled extraction logic.
+pub struct TypedResponseParser<T>(std::marker::PhantomData<T>);
+
+impl<T: serde::de::DeserializeOwned> ResponseParser for TypedResponseParser<T> {
+  type ValueType = T;
 
   fn parse_response(json: JsonValue) -> Result<Self::ValueType, RemoteError> {
-    if json.is_object() {
-      if let Some(data) = json.get("data") {
-        if let Some(message) = data.as_str() {
-          return Ok(message.to_string());
-        }
-      }
+    if let Some(err) = decode_error(&json) {
+      return Err(err);
     }
-    return Err(RemoteError::InvalidResponse(json));
+    let data = json.get("data").cloned().unwrap_or_else(|| json.clone());
+    serde_json::from_value(data).map_err(|_| RemoteError::InvalidResponse(json))
   }
 }
 
-pub struct ChatRelatedQuestionsResponseParser;
-impl ResponseParser for ChatRelatedQuestionsResponseParser {
-  type ValueType = Vec<JsonValue>;
+pub type ChatResponseParser = TypedResponseParser<String>;
+pub type ChatRelatedQuestionsResponseParser = TypedResponseParser<Vec<JsonValue>>;
 
-  fn parse_response(json: JsonValue) -> Result<Self::ValueType, RemoteError> {
-    if json.is_object() {
-      if let Some(data) = json.get("data") {
-        if let Some(values) = data.as_array() {
-          return Ok(values.clone());
-        }
-      }
-    }
-    return Err(RemoteError::InvalidResponse(json));
-  }
+#[derive(Debug, Clone, serde::Deserialize)]
+struct Score {
+  score: f64,
 }
 
+/// The similarity backend nests its score one level deeper than
+/// [`TypedResponseParser`] extracts on its own (`"data": {"score": ...}`
+/// rather than a bare number), so this keeps the original `f64`
+/// `ValueType` callers already depend on instead of leaking that shape.
 pub struct SimilarityResponseParser;
 impl ResponseParser for SimilarityResponseParser {
   type ValueType = f64;
 
   fn parse_response(json: JsonValue) -> Result<Self::ValueType, RemoteError> {
-    if json.is_object() {
-      if let Some(data) = json.get("data") {
-        if let Some(score) = data.get("score").and_then(|v| v.as_f64()) {
-          return Ok(score);
+    TypedResponseParser::<Score>::parse_response(json).map(|score| score.score)
+  }
+}
+
+/// A method name used for `"stream"` notifications: incremental chat
+/// output delivered before the call it belongs to has fully completed.
+pub const STREAM_METHOD: &str = "stream";
+
+/// A single chunk of a streaming response.
+#[derive(Debug, Clone)]
+pub enum StreamChunk {
+  /// A partial piece of the response, not yet the full answer.
+  Delta(String),
+  /// The stream has finished; no further deltas will arrive for this id.
+  Done,
+}
+
+/// Parses the `params` of a `"stream"` notification, shaped as
+/// `{"id": <RequestId>, "delta": "..."}` or `{"id": <RequestId>, "done": true}`,
+/// into a [`StreamChunk`] so callers don't have to buffer the whole answer
+/// before showing anything.
+pub struct StreamResponseParser;
+
+impl StreamResponseParser {
+  /// The [`RequestId`] of the original call this chunk belongs to, used
+  /// to route it to the right streaming handler.
+  pub fn stream_id(params: &JsonValue) -> Option<RequestId> {
+    params.get("id").and_then(JsonValue::as_u64)
+  }
+
+  pub fn parse_chunk(params: &JsonValue) -> Result<StreamChunk, RemoteError> {
+    if params.get("done").and_then(JsonValue::as_bool) == Some(true) {
+      return Ok(StreamChunk::Done);
+    }
+    match params.get("delta").and_then(JsonValue::as_str) {
+      Some(delta) => Ok(StreamChunk::Delta(delta.to_string())),
+      None => Err(RemoteError::InvalidResponse(params.clone())),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn malformed_response_line_still_resolves_its_pending_id() {
+    let reader = MessageReader::default();
+    // A response whose id is a numeral string: `RpcObject::decode` can't
+    // classify it (get_id only accepts a JSON number), but the pending
+    // call waiting on id 42 should still be resolved, with an error,
+    // instead of hanging forever.
+    let message = reader.parse(r#"{"id": "42"}"#).unwrap();
+    match message {
+      Message::Response { id, result } => {
+        assert_eq!(id, 42);
+        assert!(result.is_err());
+      },
+      other => panic!("expected Response, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn unrecoverable_line_still_errors() {
+    let reader = MessageReader::default();
+    assert!(reader.parse(r#"{"foo": "bar"}"#).is_err());
+  }
+
+  #[test]
+  fn response_parser_decodes_structured_error_before_checking_shape() {
+    let payload = json!({"error": {"code": -32601, "message": "method not found"}});
+    let err = ChatResponseParser::parse_response(payload).unwrap_err();
+    assert!(matches!(err, RemoteError::Rpc { code: -32601, .. }));
+  }
+
+  #[test]
+  fn similarity_response_parser_still_returns_a_bare_f64() {
+    let payload = json!({"data": {"score": 0.87}});
+    let score: f64 = SimilarityResponseParser::parse_response(payload).unwrap();
+    assert_eq!(score, 0.87);
+  }
+
+  #[test]
+  fn next_dispatching_streams_routes_stream_notifications_and_surfaces_nothing() {
+    use crate::core::rpc_peer::StreamHandlers;
+    use std::sync::{Arc, Mutex};
+
+    let handlers = StreamHandlers::default();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen_clone = seen.clone();
+    handlers.register(7, Box::new(move |chunk| seen_clone.lock().unwrap().push(chunk)));
+
+    let mut reader = MessageReader::default();
+    let line = br#"{"method":"stream","params":{"id":7,"delta":"hi"}}
+"#;
+    let message = reader
+      .next_dispatching_streams(&mut &line[..], &handlers)
+      .unwrap();
+
+    assert!(message.is_none());
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert!(matches!(seen[0], StreamChunk::Delta(ref delta) if delta == "hi"));
+  }
+
+  #[test]
+  fn next_dispatching_streams_passes_through_non_stream_messages() {
+    use crate::core::rpc_peer::StreamHandlers;
+
+    let handlers = StreamHandlers::default();
+    let mut reader = MessageReader::default();
+    let line = br#"{"id":1,"method":"chat","params":{"q":"hi"}}
+"#;
+    let message = reader
+      .next_dispatching_streams(&mut &line[..], &handlers)
+      .unwrap();
+
+    assert!(matches!(message, Some(Message::Request { id: 1, .. })));
+  }
+
+  #[test]
+  fn empty_batch_is_an_invalid_request() {
+    let reader = MessageReader::default();
+    assert!(matches!(reader.parse("[]"), Err(ReadError::NotObject(_))));
+  }
+
+  #[test]
+  fn batch_of_only_notifications_expects_no_response() {
+    let reader = MessageReader::default();
+    let message = reader
+      .parse(r#"[{"method":"a"},{"method":"b"}]"#)
+      .unwrap();
+    assert!(!message.expects_response());
+  }
+
+  #[test]
+  fn malformed_batch_element_with_recoverable_id_becomes_an_error_response() {
+    let reader = MessageReader::default();
+    let message = reader
+      .parse(r#"[{"method":"a"},{"id":"9"}]"#)
+      .unwrap();
+
+    match message {
+      Message::Batch(messages) => {
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[0], Message::Notification { ref method, .. } if method == "a"));
+        match &messages[1] {
+          Message::Response { id, result } => {
+            assert_eq!(*id, 9);
+            assert!(result.is_err());
+          },
+          other => panic!("expected an error Response for the malformed element, got {:?}", other),
         }
-      }
+      },
+      other => panic!("expected Batch, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn malformed_batch_element_without_recoverable_id_is_dropped_not_defaulted_to_zero() {
+    let reader = MessageReader::default();
+    // `{"foo":"bar"}` can't be classified and carries no `"id"` at all, so
+    // it must be dropped rather than fabricating `Message::Response{id: 0, ..}`,
+    // which could incorrectly resolve an unrelated pending call with id 0.
+    let message = reader
+      .parse(r#"[{"method":"a"},{"foo":"bar"}]"#)
+      .unwrap();
+
+    match message {
+      Message::Batch(messages) => {
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0], Message::Notification { ref method, .. } if method == "a"));
+      },
+      other => panic!("expected Batch, got {:?}", other),
     }
+  }
+
+  #[test]
+  fn batch_decodes_multiple_requests_keeping_each_ids_method_and_params() {
+    let reader = MessageReader::default();
+    let message = reader
+      .parse(r#"[{"id":1,"method":"related_questions","params":{"q":"hi"}},{"id":2,"method":"similarity","params":{"a":"x","b":"y"}}]"#)
+      .unwrap();
 
-    return Err(RemoteError::InvalidResponse(json));
+    match message {
+      Message::Batch(messages) => {
+        assert_eq!(messages.len(), 2);
+        match &messages[0] {
+          Message::Request { id, method, params } => {
+            assert_eq!(*id, 1);
+            assert_eq!(method, "related_questions");
+            assert_eq!(*params, Some(json!({"q": "hi"})));
+          },
+          other => panic!("expected Request, got {:?}", other),
+        }
+        match &messages[1] {
+          Message::Request { id, method, params } => {
+            assert_eq!(*id, 2);
+            assert_eq!(method, "similarity");
+            assert_eq!(*params, Some(json!({"a": "x", "b": "y"})));
+          },
+          other => panic!("expected Request, got {:?}", other),
+        }
+      },
+      other => panic!("expected Batch, got {:?}", other),
+    }
   }
 }
@@ -0,0 +1,116 @@
+use serde_json::Value as JsonValue;
+
+use crate::core::parser::{Message, RequestId};
+use crate::core::rpc_peer::ResponsePayload;
+use crate::error::{ReadError, RemoteError};
+
+/// A JSON value known to be a JSON object, read off the wire but not yet
+/// classified as a request, notification, or response.
+#[derive(Debug, Clone)]
+pub struct RpcObject(pub JsonValue);
+
+impl RpcObject {
+  /// The `"id"` member, if present.
+  pub fn get_id(&self) -> Option<RequestId> {
+    self.0.get("id").and_then(JsonValue::as_u64)
+  }
+
+  /// The `"method"` member, if present.
+  pub fn get_method(&self) -> Option<&str> {
+    self.0.get("method").and_then(JsonValue::as_str)
+  }
+
+  /// Whether this object carries the JSON-RPC 2.0 `"jsonrpc":"2.0"` marker.
+  pub fn is_jsonrpc_v2(&self) -> bool {
+    self.0.get("jsonrpc").and_then(JsonValue::as_str) == Some("2.0")
+  }
+
+  /// Classifies this object per the JSON-RPC 2.0 spec: a `method` with no
+  /// `id` is a notification, a `method` with an `id` is a request, and a
+  /// `result`/`error` member makes it a response to a prior request. An
+  /// explicit `"jsonrpc"` marker that isn't `"2.0"` is rejected outright.
+  pub fn decode(mut self) -> Result<Message, ReadError> {
+    if self.0.get("jsonrpc").is_some() && !self.is_jsonrpc_v2() {
+      return Err(ReadError::UnsupportedVersion(self.0.to_string()));
+    }
+
+    let id = self.get_id();
+    let method = self.get_method().map(str::to_string);
+    let params = self.0.get_mut("params").map(JsonValue::take);
+
+    match (method, id) {
+      (Some(method), Some(id)) => Ok(Message::Request { id, method, params }),
+      (Some(method), None) => Ok(Message::Notification { method, params }),
+      (None, Some(id)) => Ok(Message::Response {
+        id,
+        result: self.into_response_payload(),
+      }),
+      (None, None) => Err(ReadError::NotObject(self.0.to_string())),
+    }
+  }
+
+  fn into_response_payload(mut self) -> ResponsePayload {
+    if let Some(error) = self.0.get_mut("error").map(JsonValue::take) {
+      Err(RemoteError::from_error_object(error))
+    } else if let Some(result) = self.0.get_mut("result").map(JsonValue::take) {
+      Ok(result)
+    } else {
+      Err(RemoteError::InvalidResponse(self.0))
+    }
+  }
+}
+
+impl From<JsonValue> for RpcObject {
+  fn from(val: JsonValue) -> RpcObject {
+    RpcObject(val)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn decodes_request() {
+    let object = RpcObject(json!({"jsonrpc": "2.0", "id": 1, "method": "chat", "params": {"q": "hi"}}));
+    match object.decode().unwrap() {
+      Message::Request { id, method, params } => {
+        assert_eq!(id, 1);
+        assert_eq!(method, "chat");
+        assert_eq!(params, Some(json!({"q": "hi"})));
+      },
+      other => panic!("expected Request, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn decodes_notification() {
+    let object = RpcObject(json!({"method": "stream", "params": {"delta": "hi"}}));
+    match object.decode().unwrap() {
+      Message::Notification { method, params } => {
+        assert_eq!(method, "stream");
+        assert_eq!(params, Some(json!({"delta": "hi"})));
+      },
+      other => panic!("expected Notification, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn decodes_response() {
+    let object = RpcObject(json!({"id": 7, "result": "ok"}));
+    match object.decode().unwrap() {
+      Message::Response { id, result } => {
+        assert_eq!(id, 7);
+        assert_eq!(result.unwrap(), json!("ok"));
+      },
+      other => panic!("expected Response, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn rejects_unsupported_jsonrpc_version() {
+    let object = RpcObject(json!({"jsonrpc": "1.0", "id": 1, "method": "chat"}));
+    assert!(matches!(object.decode(), Err(ReadError::UnsupportedVersion(_))));
+  }
+}
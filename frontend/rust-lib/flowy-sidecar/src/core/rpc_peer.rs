@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::{json, Value as JsonValue};
+
+use crate::core::parser::{RequestId, StreamChunk};
+use crate::error::RemoteError;
+
+/// The payload of a JSON-RPC response: either the `result` value sent
+/// back by the peer, or the `error` it reported instead.
+pub type ResponsePayload = Result<JsonValue, RemoteError>;
+
+/// Builds the JSON-RPC 2.0 response object for a single call.
+pub fn build_response(id: RequestId, result: ResponsePayload) -> JsonValue {
+  match result {
+    Ok(value) => json!({"jsonrpc": "2.0", "id": id, "result": value}),
+    Err(err) => json!({"jsonrpc": "2.0", "id": id, "error": remote_error_to_json(err)}),
+  }
+}
+
+fn remote_error_to_json(err: RemoteError) -> JsonValue {
+  match err {
+    RemoteError::Rpc { code, message, data } => json!({"code": code, "message": message, "data": data}),
+    RemoteError::InvalidResponse(value) => json!({"code": -32603, "message": "internal error", "data": value}),
+  }
+}
+
+/// Assembles the JSON-RPC 2.0 response for a batch of calls, correlating
+/// each by its id — callers only pass the `(id, result)` pairs produced
+/// by handling a batch's `Message::Request` elements, never its
+/// notifications, since a batch made up entirely of notifications
+/// produces no response at all (this returns `None` rather than an
+/// empty array in that case).
+pub fn build_batch_response(results: Vec<(RequestId, ResponsePayload)>) -> Option<JsonValue> {
+  if results.is_empty() {
+    return None;
+  }
+  let responses: Vec<JsonValue> = results
+    .into_iter()
+    .map(|(id, result)| build_response(id, result))
+    .collect();
+  Some(JsonValue::Array(responses))
+}
+
+/// Receives the chunks of a single streaming response, in order.
+pub type StreamHandler = Box<dyn FnMut(StreamChunk) + Send>;
+
+/// Tracks the streaming handlers registered for in-flight calls, keyed by
+/// the [`RequestId`] of the request that started the stream.
+#[derive(Default)]
+pub struct StreamHandlers(Mutex<HashMap<RequestId, StreamHandler>>);
+
+impl StreamHandlers {
+  /// Registers a handler to receive the chunks of the stream started by
+  /// request `id`.
+  pub fn register(&self, id: RequestId, handler: StreamHandler) {
+    self.0.lock().unwrap().insert(id, handler);
+  }
+
+  /// Routes a single chunk to the handler registered for `id`, if any,
+  /// dropping the handler once the stream reports [`StreamChunk::Done`].
+  pub fn dispatch(&self, id: RequestId, chunk: StreamChunk) {
+    let mut handlers = self.0.lock().unwrap();
+    let done = matches!(chunk, StreamChunk::Done);
+    if let Some(handler) = handlers.get_mut(&id) {
+      handler(chunk);
+    }
+    if done {
+      handlers.remove(&id);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::{Arc, Mutex as StdMutex};
+
+  #[test]
+  fn dispatches_deltas_then_drops_handler_on_done() {
+    let handlers = StreamHandlers::default();
+    let seen = Arc::new(StdMutex::new(Vec::new()));
+    let seen_clone = seen.clone();
+    handlers.register(7, Box::new(move |chunk| seen_clone.lock().unwrap().push(chunk)));
+
+    handlers.dispatch(7, StreamChunk::Delta("Hel".to_string()));
+    handlers.dispatch(7, StreamChunk::Delta("lo".to_string()));
+    handlers.dispatch(7, StreamChunk::Done);
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 3);
+    assert!(matches!(seen[0], StreamChunk::Delta(ref d) if d == "Hel"));
+    assert!(matches!(seen[1], StreamChunk::Delta(ref d) if d == "lo"));
+    assert!(matches!(seen[2], StreamChunk::Done));
+
+    // The handler is dropped once the stream completes, so a further
+    // dispatch for the same id is a silent no-op rather than a panic.
+    handlers.dispatch(7, StreamChunk::Delta("ignored".to_string()));
+    assert_eq!(seen.len(), 3);
+  }
+
+  #[test]
+  fn build_batch_response_correlates_each_id_with_its_result() {
+    let results = vec![
+      (1, Ok(json!("first"))),
+      (
+        2,
+        Err(RemoteError::Rpc {
+          code: -32601,
+          message: "method not found".to_string(),
+          data: None,
+        }),
+      ),
+    ];
+
+    let response = build_batch_response(results).unwrap();
+    assert_eq!(
+      response,
+      json!([
+        {"jsonrpc": "2.0", "id": 1, "result": "first"},
+        {"jsonrpc": "2.0", "id": 2, "error": {"code": -32601, "message": "method not found", "data": null}},
+      ])
+    );
+  }
+
+  #[test]
+  fn build_batch_response_is_none_for_all_notifications() {
+    assert!(build_batch_response(Vec::new()).is_none());
+  }
+}
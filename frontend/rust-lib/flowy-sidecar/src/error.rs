@@ -0,0 +1,155 @@
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::fmt;
+
+use crate::core::parser::RequestId;
+
+/// Errors that can occur while reading a line from the underlying
+/// transport and turning it into an [`crate::core::rpc_object::RpcObject`].
+#[derive(Debug)]
+pub enum ReadError {
+  Io(std::io::Error),
+  NotObject(String),
+  /// The object carried an explicit `"jsonrpc"` marker that wasn't `"2.0"`.
+  UnsupportedVersion(String),
+  Disconnect,
+}
+
+impl From<std::io::Error> for ReadError {
+  fn from(err: std::io::Error) -> ReadError {
+    ReadError::Io(err)
+  }
+}
+
+impl fmt::Display for ReadError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ReadError::Io(err) => write!(f, "I/O error: {}", err),
+      ReadError::NotObject(s) => write!(f, "line was not a JSON object: {}", s),
+      ReadError::UnsupportedVersion(s) => write!(f, "unsupported jsonrpc version: {}", s),
+      ReadError::Disconnect => write!(f, "peer disconnected"),
+    }
+  }
+}
+
+impl std::error::Error for ReadError {}
+
+/// An error returned by the remote peer, or encountered while trying to
+/// make sense of one of its responses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteError {
+  /// A response whose shape did not match what was expected.
+  InvalidResponse(JsonValue),
+  /// A well-formed JSON-RPC 2.0 `"error"` object returned by the peer.
+  Rpc {
+    code: i64,
+    message: String,
+    data: Option<JsonValue>,
+  },
+}
+
+impl RemoteError {
+  /// Parses a JSON-RPC 2.0 `"error"` object (`{"code", "message", "data"}`)
+  /// into a structured [`RemoteError::Rpc`]. Falls back to
+  /// [`RemoteError::InvalidResponse`] if the object doesn't have the shape
+  /// the spec requires.
+  pub fn from_error_object(value: JsonValue) -> RemoteError {
+    let code = value.get("code").and_then(JsonValue::as_i64);
+    let message = value.get("message").and_then(JsonValue::as_str).map(str::to_string);
+    match (code, message) {
+      (Some(code), Some(message)) => RemoteError::Rpc {
+        code,
+        message,
+        data: value.get("data").cloned(),
+      },
+      _ => RemoteError::InvalidResponse(value),
+    }
+  }
+}
+
+impl fmt::Display for RemoteError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      RemoteError::InvalidResponse(value) => write!(f, "invalid response: {}", value),
+      RemoteError::Rpc { code, message, .. } => {
+        write!(f, "{} ({}): {}", code, reserved_code_name(*code), message)
+      },
+    }
+  }
+}
+
+impl std::error::Error for RemoteError {}
+
+/// Names the reserved JSON-RPC 2.0 error codes; application-defined codes
+/// (anything outside -32768..=-32000) are simply labeled "server error".
+fn reserved_code_name(code: i64) -> &'static str {
+  match code {
+    -32700 => "parse error",
+    -32600 => "invalid request",
+    -32601 => "method not found",
+    -32602 => "invalid params",
+    -32603 => "internal error",
+    _ => "server error",
+  }
+}
+
+/// A minimal shape used to recover the `id` of a response that otherwise
+/// failed to parse, so the pending call it belongs to can still be
+/// resolved (with an error) instead of waiting forever.
+#[derive(Debug, Deserialize)]
+pub struct JustId {
+  id: JsonValue,
+}
+
+impl JustId {
+  /// Best-effort coercion of a JSON `"id"` into a [`RequestId`]. Covers a
+  /// shape `RpcObject::get_id`'s plain `as_u64` can't: a numeral sent as
+  /// a JSON string (e.g. `"id": "42"`), which some servers emit.
+  pub fn as_request_id(&self) -> Option<RequestId> {
+    self.id.as_u64().or_else(|| self.id.as_str().and_then(|s| s.parse().ok()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn decodes_well_formed_error_object() {
+    let error = RemoteError::from_error_object(json!({
+      "code": -32601,
+      "message": "method not found",
+      "data": {"method": "chat"},
+    }));
+    assert_eq!(
+      error,
+      RemoteError::Rpc {
+        code: -32601,
+        message: "method not found".to_string(),
+        data: Some(json!({"method": "chat"})),
+      }
+    );
+  }
+
+  #[test]
+  fn falls_back_to_invalid_response_for_malformed_error_object() {
+    let value = json!({"message": "missing a code"});
+    assert_eq!(
+      RemoteError::from_error_object(value.clone()),
+      RemoteError::InvalidResponse(value)
+    );
+  }
+
+  #[test]
+  fn recovers_numeral_string_id() {
+    let just_id: JustId = serde_json::from_str(r#"{"id": "42"}"#).unwrap();
+    assert_eq!(just_id.as_request_id(), Some(42));
+  }
+
+  #[test]
+  fn rejects_non_numeral_id() {
+    let just_id: JustId = serde_json::from_str(r#"{"id": "not-a-number"}"#).unwrap();
+    assert_eq!(just_id.as_request_id(), None);
+  }
+}